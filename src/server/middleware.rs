@@ -0,0 +1,295 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::Sender;
+
+use super::request::Request;
+use super::response::Response;
+
+/// Handle passed to each middleware so it can stop the chain early.
+pub struct MiddlewareSession {
+    sender: Sender<bool>,
+}
+
+impl MiddlewareSession {
+    pub fn new(sender: Sender<bool>) -> MiddlewareSession {
+        MiddlewareSession { sender }
+    }
+
+    /// Stops the middleware chain; no further handlers run for this request.
+    pub fn stop(&self) {
+        let _ = self.sender.send(false);
+    }
+
+    /// Continues to the next handler in the chain.
+    pub fn next(&self) {
+        let _ = self.sender.send(true);
+    }
+}
+
+/// Anything that can act as a route handler / middleware step.
+pub trait MiddlewareMethod: Send + Sync {
+    fn call(&self, request: &Request, response: &mut Response, session: MiddlewareSession);
+}
+
+impl<F> MiddlewareMethod for F
+where
+    F: Fn(&Request, &mut Response, MiddlewareSession) + Send + Sync,
+{
+    fn call(&self, request: &Request, response: &mut Response, session: MiddlewareSession) {
+        self(request, response, session)
+    }
+}
+
+/// Forwards matched requests to an upstream origin, turning a mounted route
+/// into a small reverse proxy.
+///
+/// `mount` is the path the owning router is mounted at; it is stripped from
+/// the request line (the same `trim_start_matches` trick `find_route` uses)
+/// before the request is replayed to `upstream`.
+pub struct Proxy {
+    upstream: String,
+    mount: String,
+}
+
+impl Proxy {
+    /// Builds a proxy forwarding to `upstream` (e.g. `127.0.0.1:9000`) for a
+    /// router mounted at `mount`.
+    pub fn new<U: ToString, M: ToString>(upstream: U, mount: M) -> Proxy {
+        Proxy {
+            upstream: upstream.to_string(),
+            mount: mount.to_string(),
+        }
+    }
+
+    /// Opens the upstream connection and replays the request onto it.
+    fn forward(&self, request: &Request) -> Result<TcpStream, std::io::Error> {
+        let mut upstream = TcpStream::connect(&self.upstream)?;
+
+        let path = request.route.trim_start_matches(&self.mount);
+        let path = if path.is_empty() { "/" } else { path };
+        write!(upstream, "{} {} {}\r\n", request.method, path, request.version)?;
+
+        for (key, value) in request.headers.iter() {
+            // Host, Connection and the forwarding headers are consolidated and
+            // rewritten below; everything else is replayed as-is.
+            if key == "host"
+                || key == "connection"
+                || key == "x-forwarded-for"
+                || key == "x-forwarded-proto"
+            {
+                continue;
+            }
+            write!(upstream, "{}: {}\r\n", key, value)?;
+        }
+        write!(upstream, "Host: {}\r\n", self.upstream)?;
+        // Force `close` to the upstream: a kept-alive upstream never sends
+        // EOF, so `copy_upstream`'s `read_to_end` would block a pool thread
+        // forever.
+        write!(upstream, "Connection: close\r\n")?;
+
+        // Append the connecting client to any inbound chain so the upstream
+        // learns the real client IP, and mark the protocol we accepted on.
+        let forwarded = match (request.header("x-forwarded-for"), request.peer) {
+            (Some(existing), Some(peer)) => Some(format!("{}, {}", existing, peer.ip())),
+            (Some(existing), None) => Some(existing.clone()),
+            (None, Some(peer)) => Some(peer.ip().to_string()),
+            (None, None) => None,
+        };
+        if let Some(forwarded) = forwarded {
+            write!(upstream, "X-Forwarded-For: {}\r\n", forwarded)?;
+        }
+        write!(upstream, "X-Forwarded-Proto: http\r\n")?;
+
+        write!(upstream, "\r\n")?;
+        if !request.body.is_empty() {
+            upstream.write_all(&request.body)?;
+        }
+        upstream.flush()?;
+
+        Ok(upstream)
+    }
+}
+
+impl MiddlewareMethod for Proxy {
+    fn call(&self, request: &Request, response: &mut Response, _session: MiddlewareSession) {
+        let upstream = match self.forward(request) {
+            Ok(upstream) => upstream,
+            Err(_) => {
+                // Surface connection failures as 502 rather than panicking.
+                let _ = response.status(502).send("Bad Gateway");
+                return;
+            }
+        };
+
+        if copy_upstream(upstream, response).is_err() {
+            let _ = response.status(502).send("Bad Gateway");
+        }
+    }
+}
+
+/// How a [`Cors`] middleware decides whether a request's `Origin` is allowed.
+pub enum AllowedOrigins {
+    /// Allow any origin. The requesting origin is still echoed explicitly
+    /// (never `*`) so it composes with credentialed requests.
+    Any,
+    /// Allow an explicit list of origins.
+    List(Vec<String>),
+    /// Allow origins for which the predicate returns `true`.
+    Predicate(Box<dyn Fn(&str) -> bool + Send + Sync>),
+}
+
+/// Cross-origin resource sharing middleware with a configurable allow-list.
+///
+/// On a request carrying an `Origin` header that matches the allow-list it
+/// echoes `Access-Control-Allow-Origin` (plus `Vary: Origin` so caches
+/// compose multiple allowed origins) and, for an `OPTIONS` preflight,
+/// short-circuits the chain with a `204 No Content` advertising the allowed
+/// methods, headers, and max-age.
+pub struct Cors {
+    origins: AllowedOrigins,
+    methods: Vec<String>,
+    headers: Vec<String>,
+    max_age: Option<u32>,
+    credentials: bool,
+}
+
+impl Cors {
+    /// Starts a CORS policy for the given origin allow-list.
+    pub fn new(origins: AllowedOrigins) -> Cors {
+        Cors {
+            origins,
+            methods: vec![
+                "GET".to_string(),
+                "POST".to_string(),
+                "PUT".to_string(),
+                "PATCH".to_string(),
+                "DELETE".to_string(),
+                "OPTIONS".to_string(),
+            ],
+            headers: vec!["Content-Type".to_string()],
+            max_age: None,
+            credentials: false,
+        }
+    }
+
+    /// Sets the methods advertised on preflight responses.
+    pub fn methods<S: ToString>(mut self, methods: Vec<S>) -> Cors {
+        self.methods = methods.iter().map(|m| m.to_string()).collect();
+        self
+    }
+
+    /// Sets the request headers advertised on preflight responses.
+    pub fn headers<S: ToString>(mut self, headers: Vec<S>) -> Cors {
+        self.headers = headers.iter().map(|h| h.to_string()).collect();
+        self
+    }
+
+    /// Sets `Access-Control-Max-Age` (seconds) for preflight caching.
+    pub fn max_age(mut self, seconds: u32) -> Cors {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Enables credentialed requests, adding `Access-Control-Allow-Credentials`.
+    pub fn credentials(mut self, credentials: bool) -> Cors {
+        self.credentials = credentials;
+        self
+    }
+
+    fn allowed(&self, origin: &str) -> bool {
+        match self.origins {
+            AllowedOrigins::Any => true,
+            AllowedOrigins::List(ref list) => list.iter().any(|allowed| allowed == origin),
+            AllowedOrigins::Predicate(ref predicate) => predicate(origin),
+        }
+    }
+}
+
+impl MiddlewareMethod for Cors {
+    fn call(&self, request: &Request, response: &mut Response, session: MiddlewareSession) {
+        let origin = match request.header("origin") {
+            Some(origin) => origin.clone(),
+            // Not a cross-origin request; leave it to the rest of the chain.
+            None => return,
+        };
+
+        if !self.allowed(&origin) {
+            return;
+        }
+
+        // Echo the concrete origin (not `*`) so credentialed caching composes.
+        response.header("Access-Control-Allow-Origin", &origin);
+        response.header("Vary", "Origin");
+        if self.credentials {
+            response.header("Access-Control-Allow-Credentials", "true");
+        }
+
+        if request.method.eq_ignore_ascii_case("OPTIONS") {
+            response.header("Access-Control-Allow-Methods", self.methods.join(", "));
+            response.header("Access-Control-Allow-Headers", self.headers.join(", "));
+            if let Some(max_age) = self.max_age {
+                response.header("Access-Control-Max-Age", max_age);
+            }
+            let _ = response.send_status(204);
+            session.stop();
+        }
+    }
+}
+
+/// Whether `key` names a hop-by-hop header that applies to a single transport
+/// connection and must not be forwarded through the proxy.
+fn is_hop_by_hop(key: &str) -> bool {
+    const HOP_BY_HOP: [&str; 8] = [
+        "connection",
+        "keep-alive",
+        "transfer-encoding",
+        "te",
+        "trailer",
+        "upgrade",
+        "proxy-authenticate",
+        "proxy-authorization",
+    ];
+    HOP_BY_HOP.iter().any(|name| key.eq_ignore_ascii_case(name))
+}
+
+/// Copies the upstream status line, headers, and body back to the client.
+fn copy_upstream(upstream: TcpStream, response: &mut Response) -> Result<(), std::io::Error> {
+    let mut reader = BufReader::new(upstream);
+
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let mut parts = status_line.trim_end().split_whitespace();
+    let _version = parts.next();
+    let status = parts
+        .next()
+        .and_then(|code| code.parse::<u16>().ok())
+        .unwrap_or(502);
+    response.status(status);
+
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(split) = header.find(':') {
+            let key = header[..split].trim();
+            let value = header[split + 1..].trim();
+            // Content-Length is recomputed by `send`; don't double up. Drop
+            // hop-by-hop headers too — forwarding the upstream's forced
+            // `Connection: close` or a chunked `Transfer-Encoding` alongside
+            // the recomputed length would mis-frame and desync the kept-alive
+            // client connection.
+            if !key.eq_ignore_ascii_case("content-length") && !is_hop_by_hop(key) {
+                response.header(key, value);
+            }
+        }
+    }
+
+    let mut body = Vec::new();
+    reader.read_to_end(&mut body)?;
+    response.send(body)?;
+
+    Ok(())
+}
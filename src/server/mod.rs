@@ -5,14 +5,18 @@ pub mod middleware;
 pub mod session;
 mod thread_pool;
 
-use std::net::{TcpListener, TcpStream};
-use std::io::{Error, ErrorKind};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::io::{BufReader, Error, ErrorKind};
 use std::sync::{Arc, RwLock};
 use std::collections::HashMap;
 use std::sync::mpsc::channel;
+use std::time::Duration;
+use std::thread;
 use std::rc::Rc;
 use std::cell::RefCell;
 
+use socket2::{Domain, Socket, Type};
+
 use self::request::Request;
 use self::router::Router;
 use self::response::Response;
@@ -21,6 +25,10 @@ use self::session::Session;
 
 pub struct ServerInner {
     inner_routers: RwLock<HashMap<String, Router>>,
+    /// Bounds how long the full request head may take to arrive.
+    slow_request_timeout: RwLock<Option<Duration>>,
+    /// Bounds idle time between requests on a kept-alive connection.
+    keep_alive_timeout: RwLock<Option<Duration>>,
 }
 
 pub struct Server {
@@ -32,6 +40,8 @@ impl Server {
         Server {
             inner: Arc::new(ServerInner {
                 inner_routers: RwLock::new(HashMap::new()),
+                slow_request_timeout: RwLock::new(Some(Duration::from_secs(30))),
+                keep_alive_timeout: RwLock::new(Some(Duration::from_secs(5))),
             }),
         }
     }
@@ -54,40 +64,84 @@ impl Server {
     }
 
     // Parsing!
+    //
+    // Serves requests off a single connection in a loop so HTTP/1.1
+    // pipelining and persistent connections work. After each response we
+    // consult the `Connection` header (defaulting to keep-alive on HTTP/1.1,
+    // close on HTTP/1.0) to decide whether to read the next request on the
+    // same stream. Reads are bounded by the configured slow-request and
+    // keep-alive timeouts to avoid slowloris-style hangs.
     pub fn parse_incoming(&self, mut stream: &mut TcpStream) -> Result<(), Error> {
-        let mut request = Request::new(&mut stream)?;
-        // let wares = self.find_middlewares(&request.route);
+        let slow_request = *self.inner.slow_request_timeout.read().expect("Could not lock!");
+        let keep_alive = *self.inner.keep_alive_timeout.read().expect("Could not lock!");
 
-        let (path_wares, params) = self.find_route(&request.method, &request.route)?;
-        if params.len() > 0 {
-            request.params = Some(params);
-        }
+        // One BufReader for the whole connection: bytes read past a request
+        // head (a pipelined follow-up) must not be discarded between requests.
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let peer = stream.peer_addr().ok();
 
-        let stream_copy = stream.try_clone().unwrap();
-        let mut response = Response::new(stream_copy);
+        loop {
+            let mut request = match Request::new(&mut reader, &mut stream, slow_request, keep_alive) {
+                Ok(request) => request,
+                // Slow-request budget exceeded mid-head: tell the client then close.
+                Err(ref e) if e.kind() == ErrorKind::TimedOut => {
+                    let stream_copy = stream.try_clone().unwrap();
+                    let mut response = Response::new(stream_copy);
+                    let _ = response.send_status(408);
+                    return Ok(());
+                }
+                // Idle keep-alive budget elapsed, or the client closed: close silently.
+                Err(_) => return Ok(()),
+            };
+            // let wares = self.find_middlewares(&request.route);
 
-        let path_wares_rw = path_wares.clone();
-        let path_wares_ref = path_wares_rw.try_read().unwrap();
+            request.peer = peer;
 
-        let then_path_rw = path_wares_ref.then.clone();
-        let then_path_ref = then_path_rw.try_read().unwrap();
+            let (path_wares, params) = match self.find_route(&request.method, &request.route) {
+                Ok(found) => found,
+                // No matching route: answer with a real 404 instead of
+                // propagating an error and silently dropping a kept-alive
+                // connection mid-stream.
+                Err(_) => {
+                    let stream_copy = stream.try_clone().unwrap();
+                    let mut response = Response::new(stream_copy);
+                    let _ = response.send_status(404);
+                    if !keep_alive_requested(&request) {
+                        return Ok(());
+                    }
+                    continue;
+                }
+            };
+            if params.len() > 0 {
+                request.params = Some(params);
+            }
 
-        for ware in then_path_ref.iter() {
-            let (send, revc) = channel::<bool>();
-            let session = MiddlewareSession::new(send);
-            ware.call(&request, &mut response, session);
-        }
+            let stream_copy = stream.try_clone().unwrap();
+            let mut response = Response::new(stream_copy);
+            if request.method.eq_ignore_ascii_case("HEAD") {
+                response.head(true);
+            }
 
-        // let session = path_wares.clone().try_lock().unwrap();
-        // let wares = session.wares.clone();
+            let path_wares_rw = path_wares.clone();
+            let path_wares_ref = path_wares_rw.try_read().unwrap();
 
-        // for middleware in wares {
-        //         let session = MiddlewareSession::new(send);
+            let then_path_rw = path_wares_ref.then.clone();
+            let then_path_ref = then_path_rw.try_read().unwrap();
 
-        //         middleware.call(&request , &mut response, session);
-        // }
+            for ware in then_path_ref.iter() {
+                let (send, recv) = channel::<bool>();
+                let session = MiddlewareSession::new(send);
+                ware.call(&request, &mut response, session);
+                // A handler that signalled `stop` short-circuits the chain.
+                if let Ok(false) = recv.try_recv() {
+                    break;
+                }
+            }
 
-        Ok(())
+            if !keep_alive_requested(&request) {
+                return Ok(());
+            }
+        }
     }
 
     fn find_middlewares(&self, path: &String) -> Option<Arc<RwLock<Option<Session>>>> {
@@ -104,7 +158,7 @@ impl Server {
             let router_ref = router_rw.try_read().unwrap();
 
             let routing = routing.to_string();
-            if path.trim_left().starts_with(&routing) {
+            if path.trim_start().starts_with(&routing) {
                 let middlewares = router_ref.middlewares.clone();
                 return Some(middlewares);
             }
@@ -130,10 +184,10 @@ impl Server {
 
         for (routing, router) in routers {
             let routing = routing.to_string();
-            if path.trim_left().starts_with(&routing) {
+            if path.trim_start().starts_with(&routing) {
                 let (method, params) = router.find_route(
                     method.to_string(),
-                    path.trim_left_matches(&routing).to_string(),
+                    path.trim_start_matches(&routing).to_string(),
                 )?;
 
                 return Ok((method, params));
@@ -143,26 +197,124 @@ impl Server {
         return Err(Error::new(ErrorKind::NotFound, "404"));
     }
 
-    /// Attaches the Router to a port with an optional address (default loopback address IPV4)
+    /// Attaches the Router to a port across one or more addresses, binding an
+    /// IPv4 (`0.0.0.0`) and an IPv6 (`[::]`) socket by default so clients of
+    /// either family can connect.
+    ///
+    /// Each successfully bound address gets its own acceptor thread feeding a
+    /// single shared `ThreadPool`. If one family fails to bind (common where
+    /// `::` already covers v4) it is logged and skipped rather than panicking.
+    ///
+    /// `slow_request_timeout` bounds how long a request head may take to
+    /// arrive and `keep_alive_timeout` bounds idle time between requests on a
+    /// persistent connection; `None` leaves the respective default in place.
     ///
     /// # Panics if the post is closed or any other connection issue.
-    pub fn listen(self, port: i16, address: Option<String>, threads: Option<usize>) {
-        let address = address.unwrap_or(String::from("127.0.0.1"));
-        let binding =
-            TcpListener::bind(format!("{}:{}", address, port)).expect("Couldn't bind on port!");
-        let pool = thread_pool::ThreadPool::new(threads.unwrap_or(4));
+    pub fn listen(
+        self,
+        port: i16,
+        addresses: Option<Vec<String>>,
+        threads: Option<usize>,
+        slow_request_timeout: Option<Duration>,
+        keep_alive_timeout: Option<Duration>,
+    ) {
+        if let Some(timeout) = slow_request_timeout {
+            *self.inner.slow_request_timeout.write().expect("Could not lock!") = Some(timeout);
+        }
+        if let Some(timeout) = keep_alive_timeout {
+            *self.inner.keep_alive_timeout.write().expect("Could not lock!") = Some(timeout);
+        }
+
+        let addresses = addresses
+            .unwrap_or_else(|| vec!["0.0.0.0".to_string(), "[::]".to_string()]);
+        let pool = Arc::new(thread_pool::ThreadPool::new(threads.unwrap_or(4)));
         let shared_self = Arc::new(self);
 
-        for stream in binding.incoming() {
-            let mut stream = match stream {
-                Ok(v) => v,
-                Err(e) => panic!(e), // TODO: Redirect to internal Router error page.
+        let mut acceptors = Vec::new();
+        for address in addresses {
+            let binding = match bind_listener(&address, port) {
+                Some(binding) => binding,
+                None => continue, // Already logged; carry on with the other family.
             };
 
-            let self_clone = shared_self.clone();
-            pool.execute(move || {
-                self_clone.parse_incoming(&mut stream);
-            });
+            let pool = pool.clone();
+            let shared_self = shared_self.clone();
+            acceptors.push(thread::spawn(move || {
+                for stream in binding.incoming() {
+                    let mut stream = match stream {
+                        Ok(v) => v,
+                        Err(e) => panic!(e), // TODO: Redirect to internal Router error page.
+                    };
+
+                    let self_clone = shared_self.clone();
+                    pool.execute(move || {
+                        self_clone.parse_incoming(&mut stream);
+                    });
+                }
+            }));
+        }
+
+        for acceptor in acceptors {
+            let _ = acceptor.join();
         }
     }
 }
+
+/// Binds a single listener, setting `IPV6_V6ONLY` explicitly on v6 sockets so
+/// v4 and v6 behaviour is deterministic across platforms regardless of the OS
+/// default. Returns `None` (after logging) if the address can't be bound so
+/// the caller can degrade gracefully to whichever family did bind.
+fn bind_listener(address: &str, port: i16) -> Option<TcpListener> {
+    let socket_addr: SocketAddr = match format!("{}:{}", address, port).parse() {
+        Ok(addr) => addr,
+        Err(e) => {
+            eprintln!("Couldn't parse address {}: {}", address, e);
+            return None;
+        }
+    };
+
+    let domain = if socket_addr.is_ipv6() {
+        Domain::ipv6()
+    } else {
+        Domain::ipv4()
+    };
+
+    let socket = match Socket::new(domain, Type::stream(), None) {
+        Ok(socket) => socket,
+        Err(e) => {
+            eprintln!("Couldn't open socket for {}: {}", socket_addr, e);
+            return None;
+        }
+    };
+
+    // Keep the families disjoint: the v6 socket owns v6 only, the v4 socket
+    // owns v4, so a dual bind doesn't fight over v4-mapped addresses.
+    if socket_addr.is_ipv6() {
+        if let Err(e) = socket.set_only_v6(true) {
+            eprintln!("Couldn't set IPV6_V6ONLY on {}: {}", socket_addr, e);
+            return None;
+        }
+    }
+
+    if let Err(e) = socket.bind(&socket_addr.into()) {
+        eprintln!("Couldn't bind on {}: {}", socket_addr, e);
+        return None;
+    }
+
+    if let Err(e) = socket.listen(128) {
+        eprintln!("Couldn't listen on {}: {}", socket_addr, e);
+        return None;
+    }
+
+    Some(socket.into_tcp_listener())
+}
+
+/// Decides whether the connection should be kept alive for another request,
+/// honouring an explicit `Connection` header and otherwise defaulting to
+/// keep-alive on HTTP/1.1 and close on HTTP/1.0.
+fn keep_alive_requested(request: &Request) -> bool {
+    match request.header("connection") {
+        Some(value) => !value.eq_ignore_ascii_case("close"),
+        None => request.version.trim() != "HTTP/1.0",
+    }
+}
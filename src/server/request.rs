@@ -0,0 +1,293 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+/// A parsed HTTP request read off a `TcpStream`.
+pub struct Request {
+    pub method: String,
+    pub route: String,
+    pub version: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+    pub params: Option<HashMap<String, String>>,
+    /// Address of the connecting client, filled in by `parse_incoming`.
+    pub peer: Option<SocketAddr>,
+    /// Query parameters, parsed from the route on first access.
+    query: RefCell<Option<HashMap<String, Vec<String>>>>,
+    /// Cookies, parsed from the `Cookie` header on first access.
+    cookies: RefCell<Option<HashMap<String, String>>>,
+}
+
+impl Request {
+    /// Reads and parses a single request head off the stream.
+    ///
+    /// `reader` is the connection's single `BufReader`, constructed once in
+    /// `parse_incoming` and threaded through every request so bytes buffered
+    /// past one request head (a pipelined follow-up) survive into the next;
+    /// `stream` is the same underlying socket, used only to re-arm the read
+    /// timeout. `keep_alive` bounds idle time while waiting for the first byte
+    /// of the next request on a persistent connection; once that byte arrives,
+    /// `slow_request` bounds how long the rest of the head — and the body —
+    /// may take. Both are applied through `TcpStream::set_read_timeout`;
+    /// because that is a per-read timeout rather than a deadline, we track an
+    /// `Instant` and re-arm the remaining budget before each read so partial
+    /// sends accumulate but stay bounded overall.
+    ///
+    /// An elapsed keep-alive budget surfaces as `ErrorKind::WouldBlock` (the
+    /// caller should close silently); an elapsed slow-request budget surfaces
+    /// as `ErrorKind::TimedOut` (the caller should emit `408`).
+    pub fn new(
+        reader: &mut BufReader<TcpStream>,
+        stream: &mut TcpStream,
+        slow_request: Option<Duration>,
+        keep_alive: Option<Duration>,
+    ) -> Result<Request, Error> {
+        // Idle wait for the first byte of the next request; the slow-request
+        // clock only starts once something has actually arrived.
+        rearm(stream, keep_alive, Instant::now())?;
+        match reader.fill_buf() {
+            Ok(buf) if buf.is_empty() => {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "client closed"));
+            }
+            Ok(_) => {}
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                return Err(Error::new(ErrorKind::WouldBlock, "keep-alive idle"));
+            }
+            Err(e) => return Err(e),
+        }
+
+        // From the first byte on, the whole head and body must land within the
+        // slow-request budget.
+        let head_start = Instant::now();
+
+        let mut line = String::new();
+        read_line(reader, stream, &mut line, slow_request, head_start)?;
+
+        let mut parts = line.trim_end().split_whitespace();
+        let method = parts.next().unwrap_or("GET").to_string();
+        let route = parts.next().unwrap_or("/").to_string();
+        let version = parts.next().unwrap_or("HTTP/1.1").to_string();
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut header = String::new();
+            read_line(reader, stream, &mut header, slow_request, head_start)?;
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some(split) = header.find(':') {
+                let key = header[..split].trim().to_lowercase();
+                let value = header[split + 1..].trim().to_string();
+                headers.insert(key, value);
+            }
+        }
+
+        let mut body = Vec::new();
+        if let Some(len) = headers.get("content-length") {
+            if let Ok(len) = len.parse::<usize>() {
+                let mut buf = vec![0u8; len];
+                // `read_exact` issues many `read()` syscalls internally and
+                // only re-arms `SO_RCVTIMEO` once, so a trickle of bytes could
+                // pin a thread well past the deadline. Read in a re-arming loop
+                // against `head_start` (same pattern as `read_line`) so the
+                // whole body stays bounded by the slow-request budget.
+                let mut filled = 0;
+                while filled < len {
+                    rearm(stream, slow_request, head_start)?;
+                    match reader.read(&mut buf[filled..]) {
+                        Ok(0) => {
+                            return Err(Error::new(ErrorKind::UnexpectedEof, "client closed"));
+                        }
+                        Ok(read) => filled += read,
+                        // A stalled body is a slow request too: surface as 408.
+                        Err(ref e)
+                            if e.kind() == ErrorKind::WouldBlock
+                                || e.kind() == ErrorKind::TimedOut =>
+                        {
+                            return Err(Error::new(ErrorKind::TimedOut, "request timed out"));
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                body = buf;
+            }
+        }
+
+        Ok(Request {
+            method,
+            route,
+            version,
+            headers,
+            body,
+            params: None,
+            peer: None,
+            query: RefCell::new(None),
+            cookies: RefCell::new(None),
+        })
+    }
+
+    /// Looks up a request header by its lower-cased name.
+    pub fn header(&self, key: &str) -> Option<&String> {
+        self.headers.get(&key.to_lowercase())
+    }
+
+    /// Returns the first value of the query parameter `key`, if present.
+    pub fn query(&self, key: &str) -> Option<String> {
+        self.ensure_query();
+        self.query
+            .borrow()
+            .as_ref()
+            .and_then(|map| map.get(key))
+            .and_then(|values| values.first())
+            .cloned()
+    }
+
+    /// Returns every value supplied for the query parameter `key`.
+    pub fn query_all(&self, key: &str) -> Vec<String> {
+        self.ensure_query();
+        self.query
+            .borrow()
+            .as_ref()
+            .and_then(|map| map.get(key))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns the value of cookie `key`, if present.
+    pub fn cookie(&self, key: &str) -> Option<String> {
+        self.ensure_cookies();
+        self.cookies
+            .borrow()
+            .as_ref()
+            .and_then(|map| map.get(key))
+            .cloned()
+    }
+
+    /// Parses the query string (the part of the route after `?`) on demand,
+    /// URL-decoding each `key=value` pair and keeping repeated keys.
+    fn ensure_query(&self) {
+        if self.query.borrow().is_some() {
+            return;
+        }
+
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        if let Some(split) = self.route.find('?') {
+            for pair in self.route[split + 1..].split('&') {
+                if pair.is_empty() {
+                    continue;
+                }
+                let mut kv = pair.splitn(2, '=');
+                let key = decode(kv.next().unwrap_or(""));
+                let value = decode(kv.next().unwrap_or(""));
+                map.entry(key).or_insert_with(Vec::new).push(value);
+            }
+        }
+
+        *self.query.borrow_mut() = Some(map);
+    }
+
+    /// Parses the `Cookie` header on demand, splitting on `"; "`.
+    fn ensure_cookies(&self) {
+        if self.cookies.borrow().is_some() {
+            return;
+        }
+
+        let mut map = HashMap::new();
+        if let Some(header) = self.header("cookie") {
+            for pair in header.split("; ") {
+                let mut kv = pair.splitn(2, '=');
+                if let Some(key) = kv.next() {
+                    let value = kv.next().unwrap_or("");
+                    map.insert(key.trim().to_string(), value.to_string());
+                }
+            }
+        }
+
+        *self.cookies.borrow_mut() = Some(map);
+    }
+}
+
+/// URL-decodes a `form_urlencoded` component: `+` becomes a space and `%XX`
+/// becomes the decoded byte, leaving malformed escapes untouched.
+fn decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b'%' if i + 2 < bytes.len()
+                && bytes[i + 1].is_ascii_hexdigit()
+                && bytes[i + 2].is_ascii_hexdigit() => match u8::from_str_radix(&input[i + 1..i + 3], 16) {
+                Ok(byte) => {
+                    out.push(byte);
+                    i += 3;
+                }
+                Err(_) => {
+                    out.push(b'%');
+                    i += 1;
+                }
+            },
+            other => {
+                out.push(other);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Re-arms the stream's read timeout to the budget remaining since `start`,
+/// returning a timeout error once the budget is exhausted.
+fn rearm(stream: &mut TcpStream, budget: Option<Duration>, start: Instant) -> Result<(), Error> {
+    if let Some(budget) = budget {
+        match budget.checked_sub(start.elapsed()) {
+            Some(remaining) if remaining > Duration::from_millis(0) => {
+                stream.set_read_timeout(Some(remaining))?;
+            }
+            _ => return Err(Error::new(ErrorKind::TimedOut, "request timed out")),
+        }
+    } else {
+        stream.set_read_timeout(None)?;
+    }
+
+    Ok(())
+}
+
+/// Reads a single line, re-arming the remaining budget before each read and
+/// translating `WouldBlock`/`TimedOut` into a `TimedOut` signal.
+fn read_line(
+    reader: &mut BufReader<TcpStream>,
+    stream: &mut TcpStream,
+    buf: &mut String,
+    budget: Option<Duration>,
+    start: Instant,
+) -> Result<usize, Error> {
+    let mut bytes = Vec::new();
+    while bytes.last() != Some(&b'\n') {
+        rearm(stream, budget, start)?;
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                return Err(Error::new(ErrorKind::TimedOut, "request timed out"));
+            }
+            Err(e) => return Err(e),
+        };
+        let used = available.iter().position(|&b| b == b'\n').map_or(available.len(), |i| i + 1);
+        bytes.extend_from_slice(&available[..used]);
+        reader.consume(used);
+        if used == 0 {
+            break;
+        }
+    }
+    buf.push_str(&String::from_utf8_lossy(&bytes));
+    Ok(bytes.len())
+}
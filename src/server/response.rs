@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Error, Write};
+use std::net::TcpStream;
+
+/// An outgoing HTTP response bound to a client `TcpStream`.
+pub struct Response {
+    stream: TcpStream,
+    status: u16,
+    headers: HashMap<String, String>,
+    cookies: Vec<Cookie>,
+    is_head: bool,
+    sent: bool,
+}
+
+impl Response {
+    pub fn new(stream: TcpStream) -> Response {
+        Response {
+            stream,
+            status: 200,
+            headers: HashMap::new(),
+            cookies: Vec::new(),
+            is_head: false,
+            sent: false,
+        }
+    }
+
+    /// Marks this as the response to a `HEAD` request, so the computed
+    /// `Content-Length` is still sent but the body is suppressed.
+    pub fn head(&mut self, is_head: bool) -> &mut Response {
+        self.is_head = is_head;
+        self
+    }
+
+    /// Sets the status code for the response.
+    pub fn status(&mut self, status: u16) -> &mut Response {
+        self.status = status;
+        self
+    }
+
+    /// Sets a response header, replacing any existing value.
+    pub fn header<K: ToString, V: ToString>(&mut self, key: K, value: V) -> &mut Response {
+        self.headers.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Queues a `Set-Cookie` header. Multiple cookies each emit their own
+    /// header line, so this may be called repeatedly.
+    pub fn set_cookie(&mut self, cookie: Cookie) -> &mut Response {
+        self.cookies.push(cookie);
+        self
+    }
+
+    /// Writes the status line, headers, and body to the client, following RFC
+    /// framing rules so persistent connections don't desync: 1xx, 204, and 304
+    /// carry neither `Content-Length` nor a body, and a `HEAD` response keeps
+    /// the computed `Content-Length` but omits the body.
+    pub fn send<S: AsRef<[u8]>>(&mut self, body: S) -> Result<(), Error> {
+        let body = body.as_ref();
+
+        let bodyless = is_bodyless_status(self.status);
+        if bodyless {
+            self.headers.remove("Content-Length");
+        } else {
+            self.header("Content-Length", body.len());
+        }
+
+        self.write_head()?;
+        if !bodyless && !self.is_head {
+            self.stream.write_all(body)?;
+        }
+        self.stream.flush()?;
+        self.sent = true;
+        Ok(())
+    }
+
+    /// Sends an empty response carrying only the given status code.
+    pub fn send_status(&mut self, status: u16) -> Result<(), Error> {
+        self.status(status);
+        self.send("")
+    }
+
+    /// Sends `body`, honouring an optional `Range` request header value.
+    ///
+    /// With no (or an unparseable) range this behaves exactly like [`send`]
+    /// but also advertises `Accept-Ranges: bytes` for seekable bodies. A
+    /// satisfiable range yields `206 Partial Content` with a `Content-Range`
+    /// header and the sliced body; an unsatisfiable one yields `416 Range Not
+    /// Satisfiable` with `Content-Range: bytes */len`.
+    pub fn send_range<S: AsRef<[u8]>>(
+        &mut self,
+        body: S,
+        range: Option<&str>,
+    ) -> Result<(), Error> {
+        let body = body.as_ref();
+        let len = body.len();
+
+        let spec = match range.and_then(parse_range) {
+            Some(spec) => spec,
+            None => {
+                self.header("Accept-Ranges", "bytes");
+                return self.send(body);
+            }
+        };
+
+        match resolve_range(spec, len) {
+            Some((start, end)) => {
+                self.status(206);
+                self.header("Content-Range", format!("bytes {}-{}/{}", start, end, len));
+                self.header("Accept-Ranges", "bytes");
+                self.send(&body[start..=end])
+            }
+            None => {
+                self.status(416);
+                self.header("Content-Range", format!("bytes */{}", len));
+                self.send("")
+            }
+        }
+    }
+
+    /// Serialises the status line and headers onto the stream.
+    fn write_head(&mut self) -> Result<(), Error> {
+        write!(
+            self.stream,
+            "HTTP/1.1 {} {}\r\n",
+            self.status,
+            reason(self.status)
+        )?;
+        for (key, value) in self.headers.iter() {
+            write!(self.stream, "{}: {}\r\n", key, value)?;
+        }
+        for cookie in self.cookies.iter() {
+            write!(self.stream, "Set-Cookie: {}\r\n", cookie)?;
+        }
+        write!(self.stream, "\r\n")?;
+        Ok(())
+    }
+}
+
+/// A cookie to be emitted via `Set-Cookie`, with the usual attributes.
+pub struct Cookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    http_only: bool,
+    secure: bool,
+    max_age: Option<i64>,
+    same_site: Option<String>,
+}
+
+impl Cookie {
+    /// Starts a cookie with the given name and value.
+    pub fn new<N: ToString, V: ToString>(name: N, value: V) -> Cookie {
+        Cookie {
+            name: name.to_string(),
+            value: value.to_string(),
+            path: None,
+            http_only: false,
+            secure: false,
+            max_age: None,
+            same_site: None,
+        }
+    }
+
+    /// Sets the `Path` attribute.
+    pub fn path<S: ToString>(mut self, path: S) -> Cookie {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    /// Sets the `HttpOnly` attribute.
+    pub fn http_only(mut self, http_only: bool) -> Cookie {
+        self.http_only = http_only;
+        self
+    }
+
+    /// Sets the `Secure` attribute.
+    pub fn secure(mut self, secure: bool) -> Cookie {
+        self.secure = secure;
+        self
+    }
+
+    /// Sets the `Max-Age` attribute, in seconds.
+    pub fn max_age(mut self, seconds: i64) -> Cookie {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Sets the `SameSite` attribute (e.g. `Strict`, `Lax`, `None`).
+    pub fn same_site<S: ToString>(mut self, same_site: S) -> Cookie {
+        self.same_site = Some(same_site.to_string());
+        self
+    }
+}
+
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(ref path) = self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age)?;
+        }
+        if let Some(ref same_site) = self.same_site {
+            write!(f, "; SameSite={}", same_site)?;
+        }
+        if self.secure {
+            write!(f, "; Secure")?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        Ok(())
+    }
+}
+
+/// A requested byte range, before it has been resolved against a body length.
+enum RangeSpec {
+    /// `bytes=START-END`
+    FromTo(usize, usize),
+    /// `bytes=START-` (to EOF)
+    From(usize),
+    /// `bytes=-SUFFIX` (last N bytes)
+    Suffix(usize),
+}
+
+/// Parses a `Range` header value, supporting a single `bytes=` range.
+fn parse_range(header: &str) -> Option<RangeSpec> {
+    let header = header.trim();
+    if !header.starts_with("bytes=") {
+        return None;
+    }
+
+    // Only the first range of a (possibly multi-) range request is honoured.
+    let spec = header["bytes=".len()..].split(',').next().unwrap_or("").trim();
+    let dash = spec.find('-')?;
+    let start = spec[..dash].trim();
+    let end = spec[dash + 1..].trim();
+
+    match (start.is_empty(), end.is_empty()) {
+        (true, false) => end.parse::<usize>().ok().map(RangeSpec::Suffix),
+        (false, true) => start.parse::<usize>().ok().map(RangeSpec::From),
+        (false, false) => {
+            let start = start.parse::<usize>().ok()?;
+            let end = end.parse::<usize>().ok()?;
+            Some(RangeSpec::FromTo(start, end))
+        }
+        (true, true) => None,
+    }
+}
+
+/// Resolves a spec against `len` into an inclusive `(start, end)`, clamping
+/// `end` to `len - 1` and rejecting ranges that start at or past the end.
+fn resolve_range(spec: RangeSpec, len: usize) -> Option<(usize, usize)> {
+    if len == 0 {
+        return None;
+    }
+    let last = len - 1;
+
+    let (start, end) = match spec {
+        RangeSpec::FromTo(start, end) => (start, end.min(last)),
+        RangeSpec::From(start) => (start, last),
+        RangeSpec::Suffix(suffix) => {
+            if suffix == 0 {
+                return None;
+            }
+            let suffix = suffix.min(len);
+            (len - suffix, last)
+        }
+    };
+
+    if start > last || start > end {
+        return None;
+    }
+
+    Some((start, end))
+}
+
+/// Whether a status code must be sent without a `Content-Length` or body.
+fn is_bodyless_status(status: u16) -> bool {
+    status == 204 || status == 304 || (status >= 100 && status < 200)
+}
+
+/// Maps a status code to its reason phrase.
+pub fn reason(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        408 => "Request Timeout",
+        416 => "Range Not Satisfiable",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        _ => "",
+    }
+}